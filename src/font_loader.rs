@@ -0,0 +1,45 @@
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use ttf2mesh::{Face, TTFFile};
+
+/// A loaded TTF font, kept alive for the lifetime of the asset so its glyph
+/// outlines can be tessellated on demand.
+#[derive(TypeUuid)]
+#[uuid = "b5dc46f7-0d2e-4c5e-9a6d-9b0fddb8f2fd"]
+pub struct TextMeshFont {
+    pub(crate) ttf_font: Face,
+    /// Raw TTF bytes, kept alongside the parsed `ttf_font` so the optional
+    /// `shaping` feature ([`crate::shaping`]) can register the same font
+    /// with `cosmic-text`'s `fontdb` instead of only having a ttf2mesh
+    /// `Face` to tessellate from.
+    pub(crate) bytes: Vec<u8>,
+}
+
+#[derive(Default)]
+pub(crate) struct TextMeshFontLoader;
+
+impl AssetLoader for TextMeshFontLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let ttf_file = TTFFile::from_buffer_vec(bytes.to_vec())?;
+            let ttf_font = Face::from(ttf_file)?;
+
+            load_context.set_default_asset(LoadedAsset::new(TextMeshFont {
+                ttf_font,
+                bytes: bytes.to_vec(),
+            }));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ttf#mesh"]
+    }
+}
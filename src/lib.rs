@@ -0,0 +1,40 @@
+mod font_loader;
+mod mesh_cache;
+mod mesh_data_generator;
+mod mesh_system;
+#[cfg(feature = "shaping")]
+mod shaping;
+mod text_mesh;
+
+pub use font_loader::TextMeshFont;
+pub use mesh_cache::MeshCache;
+#[cfg(feature = "shaping")]
+pub use shaping::ShapingContext;
+pub use text_mesh::{DefaultTextMeshQuality, TextMesh, TextMeshColor, TextMeshQuality, TextMeshStyle};
+
+use bevy::prelude::*;
+
+/// Adds the systems and resources required to render [`TextMesh`] entities.
+///
+/// With the `shaping` feature enabled, mesh generation runs through the
+/// `cosmic-text`/`swash` shaping backend ([`shaping::text_mesh_shaped`])
+/// instead of the default per-codepoint ttf2mesh path.
+pub struct TextMeshPlugin;
+
+impl Plugin for TextMeshPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MeshCache>()
+            .init_resource::<DefaultTextMeshQuality>()
+            .add_asset::<TextMeshFont>()
+            .add_system(mesh_system::text_mesh_color)
+            .add_system(mesh_system::rescale_on_window_scale_factor_changed)
+            .add_system(mesh_system::font_loaded);
+
+        #[cfg(feature = "shaping")]
+        app.init_resource::<shaping::ShapingContext>()
+            .add_system(shaping::text_mesh_shaped);
+
+        #[cfg(not(feature = "shaping"))]
+        app.add_system(mesh_system::text_mesh);
+    }
+}
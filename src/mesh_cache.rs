@@ -0,0 +1,488 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::mesh_data_generator::MeshData;
+use crate::text_mesh::TextMeshQuality;
+
+/// Identifies which glyph a [`CacheKey`] refers to.
+///
+/// `Codepoint` is used by the default per-character ttf2mesh path. `Shaped`
+/// is used by the optional `shaping` backend (see [`crate::shaping`]), which
+/// resolves glyphs through font fallback/substitution, so the same codepoint
+/// can map to different glyphs depending on which font it was shaped with.
+///
+/// Both variants carry a `font_id` - a multi-section `TextMesh` can give
+/// each section its own font, so the same codepoint/glyph id at the same
+/// size/depth/quality can still tessellate to a different outline depending
+/// on which font it came from. Callers hash their `Handle<TextMeshFont>`
+/// (or, for `Shaped`, `cosmic_text::fontdb::ID`) down to a `u64` themselves -
+/// see `mesh_data_generator::font_handle_key` and `shaping::font_id_key`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum GlyphKey {
+    Codepoint { font_id: u64, glyph: char },
+    Shaped { font_id: u64, glyph_id: u16 },
+}
+
+/// Key identifying a single tessellated glyph: the glyph itself, its size,
+/// extrusion depth and curve-subdivision quality (all bit-cast so the key
+/// can implement `Eq`/`Hash`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey {
+    glyph: GlyphKey,
+    size_bits: u32,
+    depth_bits: u32,
+    quality_bits: u32,
+}
+
+impl CacheKey {
+    pub(crate) fn new(font_id: u64, glyph: char, size: f32, depth: f32, quality: TextMeshQuality) -> Self {
+        Self {
+            glyph: GlyphKey::Codepoint { font_id, glyph },
+            size_bits: size.to_bits(),
+            depth_bits: depth.to_bits(),
+            quality_bits: quality.curve_tolerance().to_bits(),
+        }
+    }
+
+    pub(crate) fn shaped(font_id: u64, glyph_id: u16, size: f32, depth: f32, quality: TextMeshQuality) -> Self {
+        Self {
+            glyph: GlyphKey::Shaped { font_id, glyph_id },
+            size_bits: size.to_bits(),
+            depth_bits: depth.to_bits(),
+            quality_bits: quality.curve_tolerance().to_bits(),
+        }
+    }
+}
+
+fn mesh_data_bytes(data: &MeshData) -> usize {
+    data.vertices.len() * std::mem::size_of::<[f32; 3]>()
+        + data.normals.len() * std::mem::size_of::<[f32; 3]>()
+        + data.uvs.len() * std::mem::size_of::<[f32; 2]>()
+        + data.colors.len() * std::mem::size_of::<[f32; 4]>()
+        + data.indices.len() * std::mem::size_of::<u32>()
+}
+
+/// An entry in [`MeshCache`]'s intrusive recency list, doubly-linked by
+/// `CacheKey` so touch/evict are O(1) instead of scanning a recency queue.
+#[derive(Debug)]
+struct CacheEntry {
+    data: MeshData,
+    prev: Option<CacheKey>,
+    next: Option<CacheKey>,
+}
+
+/// Caches tessellated glyph geometry so that identical
+/// `(font, glyph, size, depth, quality)` combinations are only triangulated
+/// once.
+///
+/// Bounded by entry count and/or approximate vertex-byte budget
+/// ([`MeshCache::set_capacity`]); once either is exceeded, the
+/// least-recently-used entries are evicted. Entries are only ever inserted
+/// or evicted through `&mut self` methods on the main thread - on the default
+/// path from the sequential prepare step
+/// ([`crate::mesh_data_generator::ensure_cached`]) and [`Self::end_frame`],
+/// on the `shaping` path from `shaping::shape_text_mesh` - while the parallel
+/// assemble step only ever [peeks](MeshCache::peek), so it never touches
+/// recency or triggers eviction.
+///
+/// Recency is tracked as an intrusive doubly-linked list threaded through
+/// `cache`'s entries (`head` = most recently used, `tail` = least recently
+/// used), so `touch`/`put` are O(1) regardless of cache size rather than
+/// scanning a separate recency queue.
+///
+/// Capacity can safely be set smaller than a single frame's glyph set: every
+/// key touched by [`Self::get`]/[`Self::insert`] is pinned against eviction
+/// (see `pinned` below) until [`Self::end_frame`] runs, so a `TextMesh` with
+/// more distinct glyphs than the capacity can't evict its own earlier glyphs
+/// mid-`ensure_cached` before `assemble_text_mesh` reads them back - the
+/// cache just temporarily holds more than `max_entries`/`max_bytes` until
+/// the frame's pins are released.
+#[derive(Debug, Resource)]
+pub struct MeshCache {
+    cache: HashMap<CacheKey, CacheEntry>,
+    head: Option<CacheKey>,
+    tail: Option<CacheKey>,
+    bytes_held: usize,
+    max_entries: Option<usize>,
+    max_bytes: Option<usize>,
+    hits: u64,
+    misses: u64,
+    /// Keys touched by the current frame's sequential prepare step
+    /// ([`Self::get`]/[`Self::insert`]), exempted from eviction until
+    /// [`Self::end_frame`] clears this set. Keeps a glyph inserted early in
+    /// `ensure_cached` alive for the parallel `assemble_text_mesh` pass that
+    /// reads it back later in the same frame.
+    pinned: std::collections::HashSet<CacheKey>,
+}
+
+impl Default for MeshCache {
+    fn default() -> Self {
+        Self {
+            cache: HashMap::new(),
+            head: None,
+            tail: None,
+            bytes_held: 0,
+            max_entries: None,
+            max_bytes: None,
+            hits: 0,
+            misses: 0,
+            pinned: std::collections::HashSet::new(),
+        }
+    }
+}
+
+impl MeshCache {
+    /// Looks up a codepoint-keyed glyph, marking it as most-recently-used and
+    /// updating the hit/miss counters. Used by the sequential prepare step,
+    /// which also pins the key against eviction until [`Self::end_frame`].
+    pub(crate) fn get(
+        &mut self,
+        font_id: u64,
+        glyph: char,
+        size: f32,
+        depth: f32,
+        quality: TextMeshQuality,
+    ) -> Option<&MeshData> {
+        let key = CacheKey::new(font_id, glyph, size, depth, quality);
+        self.pinned.insert(key);
+        self.touch(&key)
+    }
+
+    pub(crate) fn insert(
+        &mut self,
+        font_id: u64,
+        glyph: char,
+        size: f32,
+        depth: f32,
+        quality: TextMeshQuality,
+        data: MeshData,
+    ) {
+        let key = CacheKey::new(font_id, glyph, size, depth, quality);
+        self.pinned.insert(key);
+        self.put(key, data);
+    }
+
+    /// Looks up a shaped-glyph-keyed entry, updating recency and the hit/miss
+    /// counters like [`Self::get`]. Unlike `get`, it does not pin the key
+    /// against eviction.
+    pub(crate) fn get_shaped(
+        &mut self,
+        font_id: u64,
+        glyph_id: u16,
+        size: f32,
+        depth: f32,
+        quality: TextMeshQuality,
+    ) -> Option<&MeshData> {
+        self.touch(&CacheKey::shaped(font_id, glyph_id, size, depth, quality))
+    }
+
+    pub(crate) fn insert_shaped(
+        &mut self,
+        font_id: u64,
+        glyph_id: u16,
+        size: f32,
+        depth: f32,
+        quality: TextMeshQuality,
+        data: MeshData,
+    ) {
+        self.put(CacheKey::shaped(font_id, glyph_id, size, depth, quality), data);
+    }
+
+    /// Read-only lookup that does *not* update recency - safe to call from
+    /// the parallel assemble step via a shared `&MeshCache`.
+    pub(crate) fn peek(&self, font_id: u64, glyph: char, size: f32, depth: f32, quality: TextMeshQuality) -> Option<&MeshData> {
+        self.cache
+            .get(&CacheKey::new(font_id, glyph, size, depth, quality))
+            .map(|entry| &entry.data)
+    }
+
+    fn touch(&mut self, key: &CacheKey) -> Option<&MeshData> {
+        if !self.cache.contains_key(key) {
+            self.misses += 1;
+            return None;
+        }
+
+        self.hits += 1;
+        self.move_to_front(key);
+        self.cache.get(key).map(|entry| &entry.data)
+    }
+
+    fn put(&mut self, key: CacheKey, data: MeshData) {
+        if self.cache.contains_key(&key) {
+            self.unlink(&key);
+            let old = self.cache.remove(&key).expect("just checked contains_key");
+            self.bytes_held -= mesh_data_bytes(&old.data);
+        }
+
+        self.bytes_held += mesh_data_bytes(&data);
+        self.cache.insert(
+            key,
+            CacheEntry {
+                data,
+                prev: None,
+                next: None,
+            },
+        );
+        self.push_front(key);
+
+        self.evict_if_needed();
+    }
+
+    fn evict_if_needed(&mut self) {
+        while self.over_capacity() {
+            let Some(lru_key) = self.lru_unpinned_key() else {
+                // every remaining entry is pinned for the current frame -
+                // capacity is temporarily exceeded rather than evicting a
+                // glyph `assemble_text_mesh` still needs to read back.
+                break;
+            };
+
+            self.unlink(&lru_key);
+            if let Some(evicted) = self.cache.remove(&lru_key) {
+                self.bytes_held -= mesh_data_bytes(&evicted.data);
+            }
+        }
+    }
+
+    /// Walks the recency list from the tail (least-recently-used) for the
+    /// first entry not currently pinned by this frame's prepare step.
+    fn lru_unpinned_key(&self) -> Option<CacheKey> {
+        let mut cursor = self.tail;
+
+        while let Some(key) = cursor {
+            if !self.pinned.contains(&key) {
+                return Some(key);
+            }
+            cursor = self.cache.get(&key).and_then(|entry| entry.prev);
+        }
+
+        None
+    }
+
+    /// Detaches `key` from the recency list, patching up its neighbours (and
+    /// `head`/`tail` if it was at either end). Does not touch the `cache` map
+    /// entry itself - callers re-link it with [`Self::push_front`] or remove
+    /// it outright.
+    fn unlink(&mut self, key: &CacheKey) {
+        let (prev, next) = {
+            let entry = self.cache.get(key).expect("unlink called on a key present in cache");
+            (entry.prev, entry.next)
+        };
+
+        match prev {
+            Some(prev_key) => self.cache.get_mut(&prev_key).expect("linked neighbour exists").next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(next_key) => self.cache.get_mut(&next_key).expect("linked neighbour exists").prev = prev,
+            None => self.tail = prev,
+        }
+    }
+
+    /// Links `key` (already present in `cache`, with stale `prev`/`next`) in
+    /// as the new most-recently-used head.
+    fn push_front(&mut self, key: CacheKey) {
+        let old_head = self.head;
+
+        if let Some(entry) = self.cache.get_mut(&key) {
+            entry.prev = None;
+            entry.next = old_head;
+        }
+        if let Some(old_head_key) = old_head {
+            self.cache.get_mut(&old_head_key).expect("head exists").prev = Some(key);
+        }
+
+        self.head = Some(key);
+        self.tail.get_or_insert(key);
+    }
+
+    fn move_to_front(&mut self, key: &CacheKey) {
+        if self.head == Some(*key) {
+            return;
+        }
+
+        self.unlink(key);
+        self.push_front(*key);
+    }
+
+    fn over_capacity(&self) -> bool {
+        self.max_entries.is_some_and(|max| self.cache.len() > max)
+            || self.max_bytes.is_some_and(|max| self.bytes_held > max)
+    }
+
+    /// Sets the eviction budget. `None` for either field means "unbounded"
+    /// for that dimension. Immediately evicts if the new capacity is already
+    /// exceeded.
+    pub fn set_capacity(&mut self, max_entries: Option<usize>, max_bytes: Option<usize>) {
+        self.max_entries = max_entries;
+        self.max_bytes = max_bytes;
+        self.evict_if_needed();
+    }
+
+    /// Drops every cached entry and resets the hit/miss counters.
+    pub fn clear(&mut self) {
+        self.cache.clear();
+        self.head = None;
+        self.tail = None;
+        self.bytes_held = 0;
+        self.hits = 0;
+        self.misses = 0;
+        self.pinned.clear();
+    }
+
+    /// Releases this frame's eviction pins and re-applies the capacity
+    /// bound. Call once per frame, after the parallel `assemble_text_mesh`
+    /// pass has finished reading back everything `ensure_cached` pinned.
+    pub(crate) fn end_frame(&mut self) {
+        self.pinned.clear();
+        self.evict_if_needed();
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn bytes_held(&self) -> usize {
+        self.bytes_held
+    }
+
+    /// Fraction of [`Self::get`]/[`Self::get_shaped`] calls that found a
+    /// cached entry, in `[0.0, 1.0]`. `0.0` if nothing has been looked up yet.
+    pub fn hit_ratio(&self) -> f32 {
+        let total = self.hits + self.misses;
+
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f32 / total as f32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEPTH: f32 = 1.0;
+    const QUALITY: TextMeshQuality = TextMeshQuality::Medium;
+    const FONT_ID: u64 = 1;
+    const OTHER_FONT_ID: u64 = 2;
+
+    fn mesh_with_vertices(n: usize) -> MeshData {
+        MeshData {
+            vertices: vec![[0.0, 0.0, 0.0]; n],
+            ..MeshData::default()
+        }
+    }
+
+    #[test]
+    fn miss_then_hit_updates_counters() {
+        let mut cache = MeshCache::default();
+
+        assert!(cache.get(FONT_ID, 'a', 1.0, DEPTH, QUALITY).is_none());
+        cache.insert(FONT_ID, 'a', 1.0, DEPTH, QUALITY, MeshData::default());
+        assert!(cache.get(FONT_ID, 'a', 1.0, DEPTH, QUALITY).is_some());
+
+        assert_eq!(cache.hit_ratio(), 0.5);
+    }
+
+    #[test]
+    fn clear_resets_everything() {
+        let mut cache = MeshCache::default();
+        cache.insert(FONT_ID, 'a', 1.0, DEPTH, QUALITY, MeshData::default());
+        cache.get(FONT_ID, 'a', 1.0, DEPTH, QUALITY);
+
+        cache.clear();
+
+        assert_eq!(cache.entry_count(), 0);
+        assert_eq!(cache.bytes_held(), 0);
+        assert_eq!(cache.hit_ratio(), 0.0);
+    }
+
+    #[test]
+    fn evicts_least_recently_used_entry_when_over_entry_capacity() {
+        let mut cache = MeshCache::default();
+        cache.set_capacity(Some(2), None);
+
+        cache.insert(FONT_ID, 'a', 1.0, DEPTH, QUALITY, MeshData::default());
+        cache.insert(FONT_ID, 'b', 1.0, DEPTH, QUALITY, MeshData::default());
+        // touch `a` so `b` becomes the least recently used entry
+        cache.get(FONT_ID, 'a', 1.0, DEPTH, QUALITY);
+        cache.insert(FONT_ID, 'c', 1.0, DEPTH, QUALITY, MeshData::default());
+        // all three are still pinned from this frame's prepare step - only
+        // `end_frame` actually enforces the capacity bound
+        cache.end_frame();
+
+        assert_eq!(cache.entry_count(), 2);
+        assert!(cache.peek(FONT_ID, 'a', 1.0, DEPTH, QUALITY).is_some());
+        assert!(cache.peek(FONT_ID, 'c', 1.0, DEPTH, QUALITY).is_some());
+        assert!(cache.peek(FONT_ID, 'b', 1.0, DEPTH, QUALITY).is_none());
+    }
+
+    #[test]
+    fn evicts_down_to_byte_budget() {
+        let mut cache = MeshCache::default();
+        let entry_bytes = mesh_data_bytes(&mesh_with_vertices(1));
+        cache.set_capacity(None, Some(entry_bytes));
+
+        cache.insert(FONT_ID, 'a', 1.0, DEPTH, QUALITY, mesh_with_vertices(1));
+        cache.insert(FONT_ID, 'b', 1.0, DEPTH, QUALITY, mesh_with_vertices(1));
+        cache.end_frame();
+
+        assert_eq!(cache.entry_count(), 1);
+        assert!(cache.bytes_held() <= entry_bytes);
+        assert!(cache.peek(FONT_ID, 'a', 1.0, DEPTH, QUALITY).is_none());
+        assert!(cache.peek(FONT_ID, 'b', 1.0, DEPTH, QUALITY).is_some());
+    }
+
+    #[test]
+    fn distinct_quality_keeps_separate_cache_entries() {
+        let mut cache = MeshCache::default();
+
+        cache.insert(FONT_ID, 'a', 1.0, DEPTH, TextMeshQuality::Low, MeshData::default());
+
+        assert!(cache.peek(FONT_ID, 'a', 1.0, DEPTH, TextMeshQuality::Low).is_some());
+        assert!(cache.peek(FONT_ID, 'a', 1.0, DEPTH, TextMeshQuality::High).is_none());
+    }
+
+    #[test]
+    fn distinct_fonts_keep_separate_cache_entries() {
+        let mut cache = MeshCache::default();
+
+        // two sections using different fonts but landing on the same
+        // codepoint/size/depth/quality must not collide - each font's glyph
+        // outline can differ even for the same character.
+        cache.insert(FONT_ID, 'a', 1.0, DEPTH, QUALITY, mesh_with_vertices(1));
+        cache.insert(OTHER_FONT_ID, 'a', 1.0, DEPTH, QUALITY, mesh_with_vertices(2));
+
+        assert_eq!(cache.peek(FONT_ID, 'a', 1.0, DEPTH, QUALITY).unwrap().vertices.len(), 1);
+        assert_eq!(
+            cache.peek(OTHER_FONT_ID, 'a', 1.0, DEPTH, QUALITY).unwrap().vertices.len(),
+            2
+        );
+    }
+
+    #[test]
+    fn entries_from_the_current_frame_survive_ensure_cached_even_over_capacity() {
+        let mut cache = MeshCache::default();
+        cache.set_capacity(Some(1), None);
+
+        // a single `TextMesh` with more distinct glyphs than the capacity -
+        // `b` and `c` must not evict `a` before it's read back below, even
+        // though the cache is over its entry-count budget the whole time.
+        cache.insert(FONT_ID, 'a', 1.0, DEPTH, QUALITY, MeshData::default());
+        cache.insert(FONT_ID, 'b', 1.0, DEPTH, QUALITY, MeshData::default());
+        cache.insert(FONT_ID, 'c', 1.0, DEPTH, QUALITY, MeshData::default());
+
+        assert!(cache.peek(FONT_ID, 'a', 1.0, DEPTH, QUALITY).is_some());
+        assert!(cache.peek(FONT_ID, 'b', 1.0, DEPTH, QUALITY).is_some());
+        assert!(cache.peek(FONT_ID, 'c', 1.0, DEPTH, QUALITY).is_some());
+
+        // once assembly has read everything back, `end_frame` enforces the
+        // capacity bound again.
+        cache.end_frame();
+        assert_eq!(cache.entry_count(), 1);
+        assert!(cache.peek(FONT_ID, 'c', 1.0, DEPTH, QUALITY).is_some());
+    }
+}
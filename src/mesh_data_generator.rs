@@ -0,0 +1,158 @@
+use std::hash::{Hash, Hasher};
+
+use bevy::prelude::*;
+use ttf2mesh::{Face, Quality};
+
+use crate::font_loader::TextMeshFont;
+use crate::mesh_cache::MeshCache;
+use crate::text_mesh::{TextMesh, TextMeshQuality};
+
+const DEPTH: f32 = 1.0;
+
+/// Raw vertex data for a generated text mesh, ready to be inserted into a
+/// `bevy::render::mesh::Mesh`.
+///
+/// `colors` holds one per-vertex colour, baked in from each section's
+/// [`TextMeshSection::color`](crate::text_mesh::TextMeshSection) so a single
+/// mesh can still render multiple colours via `Mesh::ATTRIBUTE_COLOR`.
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub vertices: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub colors: Vec<[f32; 4]>,
+    pub indices: Vec<u32>,
+}
+
+/// Sequential prepare step: makes sure every glyph `text_mesh` needs this
+/// frame is tessellated and present in `cache`.
+///
+/// This is the *only* place glyph geometry is ever inserted into the cache,
+/// which is what lets [`assemble_text_mesh`] read it from multiple worker
+/// threads afterwards without any locking.
+///
+/// Returns `false` if any section's font asset hasn't loaded yet, in which
+/// case the caller should skip this `TextMesh` for now.
+pub(crate) fn ensure_cached(
+    text_mesh: &TextMesh,
+    fonts: &mut Assets<TextMeshFont>,
+    cache: &mut MeshCache,
+    quality: TextMeshQuality,
+) -> bool {
+    for section in &text_mesh.sections {
+        let font_id = font_handle_key(&section.style.font);
+        let font = match fonts.get_mut(&section.style.font) {
+            Some(font) => font,
+            None => return false,
+        };
+        let size = section.style.font_size;
+
+        for glyph in section.text.chars() {
+            if cache.get(font_id, glyph, size, DEPTH, quality).is_none() {
+                let generated = tessellate_glyph(&mut font.ttf_font, glyph, size, DEPTH, quality);
+                cache.insert(font_id, glyph, size, DEPTH, quality, generated);
+            }
+        }
+    }
+
+    true
+}
+
+/// Parallel step: concatenates the (already-cached) sub-meshes of every
+/// section of `text_mesh` along the baseline. Only reads `cache` - safe to
+/// call from any worker thread once [`ensure_cached`] has run for this mesh.
+pub(crate) fn assemble_text_mesh(text_mesh: &TextMesh, cache: &MeshCache, quality: TextMeshQuality) -> MeshData {
+    let mut data = MeshData::default();
+    let mut cursor = 0.0;
+
+    for section in &text_mesh.sections {
+        let font_id = font_handle_key(&section.style.font);
+        let size = section.style.font_size;
+        let color = section.color.as_rgba_f32();
+
+        for glyph in section.text.chars() {
+            let glyph_data = cache
+                .peek(font_id, glyph, size, DEPTH, quality)
+                .expect("ensure_cached populates every glyph before assemble_text_mesh runs");
+
+            append_glyph(&mut data, glyph_data, cursor, color);
+            cursor += size;
+        }
+    }
+
+    data
+}
+
+/// Hashes a font [`Handle`]'s id into the `u64` the default (non-shaping)
+/// path uses as a glyph's font identity in [`MeshCache`] - mirrors
+/// `shaping::font_id_key`, which does the same for `cosmic_text`'s
+/// `fontdb::ID` on the shaped path.
+fn font_handle_key(handle: &Handle<TextMeshFont>) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    handle.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn append_glyph(data: &mut MeshData, glyph_data: &MeshData, x_offset: f32, color: [f32; 4]) {
+    let index_offset = data.vertices.len() as u32;
+
+    data.vertices.extend(
+        glyph_data
+            .vertices
+            .iter()
+            .map(|[x, y, z]| [x + x_offset, *y, *z]),
+    );
+    data.normals.extend(&glyph_data.normals);
+    data.uvs.extend(&glyph_data.uvs);
+    data.colors
+        .extend(std::iter::repeat(color).take(glyph_data.vertices.len()));
+    data.indices
+        .extend(glyph_data.indices.iter().map(|i| i + index_offset));
+}
+
+fn tessellate_glyph(font: &mut Face, glyph: char, size: f32, depth: f32, quality: TextMeshQuality) -> MeshData {
+    let ttf2mesh_quality = to_ttf2mesh_quality(quality);
+
+    let Ok(mut glyph) = font.glyph(glyph) else {
+        // no outline for this codepoint (e.g. space, or missing from the font) - an
+        // empty mesh is the correct result, not a fallback
+        return MeshData::default();
+    };
+
+    let Ok(mesh) = glyph.to_3d_mesh(ttf2mesh_quality, depth) else {
+        return MeshData::default();
+    };
+
+    let mut data = MeshData::default();
+
+    for vertex in mesh.vertices() {
+        data.vertices.push([vertex.x * size, vertex.y * size, vertex.z * size]);
+        data.uvs.push([vertex.x, vertex.y]);
+    }
+    for normal in mesh.normals() {
+        data.normals.push([normal.x, normal.y, normal.z]);
+    }
+    for face in mesh.faces() {
+        data.indices.push(face.0 as u32);
+        data.indices.push(face.1 as u32);
+        data.indices.push(face.2 as u32);
+    }
+
+    data
+}
+
+/// Maps a curve tolerance onto ttf2mesh's fixed quality tiers, picking the
+/// finest tier whose tolerance is no finer than requested (e.g. `0.03` maps
+/// to `Medium`); tolerances below `High`'s map to `High`, and those above
+/// `Medium`'s map to `Low`.
+fn to_ttf2mesh_quality(quality: TextMeshQuality) -> Quality {
+    let tolerance = quality.curve_tolerance();
+
+    if tolerance <= TextMeshQuality::High.curve_tolerance() {
+        Quality::High
+    } else if tolerance <= TextMeshQuality::Medium.curve_tolerance() {
+        Quality::Medium
+    } else {
+        Quality::Low
+    }
+}
@@ -1,10 +1,19 @@
+use std::collections::HashMap;
+use std::sync::mpsc;
+
 use bevy::render::render_resource::PrimitiveTopology;
+use bevy::tasks::ComputeTaskPool;
 use bevy::{prelude::*, render::mesh::Indices};
 
 use crate::{
-    font_loader::TextMeshFont, mesh_cache::MeshCache, mesh_data_generator::generate_text_mesh,
+    font_loader::TextMeshFont,
+    mesh_cache::MeshCache,
+    mesh_data_generator::{assemble_text_mesh, ensure_cached},
+};
+use crate::{
+    mesh_data_generator::MeshData,
+    text_mesh::{DefaultTextMeshQuality, TextMesh, TextMeshColor},
 };
-use crate::{mesh_data_generator::MeshData, text_mesh::TextMesh};
 
 pub(crate) fn text_mesh(
     mut commands: Commands,
@@ -18,6 +27,7 @@ pub(crate) fn text_mesh(
             &GlobalTransform,
             Option<&Handle<StandardMaterial>>,
             &TextMesh,
+            Option<&TextMeshColor>,
             Option<&Handle<Mesh>>,
             &Visibility,
             &mut TextMeshState,
@@ -25,66 +35,107 @@ pub(crate) fn text_mesh(
         Or<(Changed<TextMesh>, Changed<TextMeshState>)>,
     >,
     mut cache: ResMut<MeshCache>,
+    default_quality: Res<DefaultTextMeshQuality>,
 ) {
     // per-text-mesh system. Triggered only if the TextMesh or TextMeshState change
     // = user changes text properties, or if/when the font is loaded
     // the initial render might happen before font has loaded - hence need to trigger after font load
     //
-    // TODO: performance could be improved by using text_meshes.par_for_each
-    // but that'd require cache to be cloneable.
-    // maybe using channels could work, e.g. pre-generate sprites to cache,
-    // then parallel execute each mesh generation and send results to channels
-    // and finally run commands/meshes additions sequentially from channel results
-    // --> requires large amount of work, performance not yet bottleneck,
-    // implement in future, if needed
+    // color changes are handled by `text_mesh_color` instead, so that recoloring
+    // a TextMesh doesn't re-run the (expensive) tessellation below
+    //
+    // Split into two phases so the (expensive) per-entity mesh assembly can run
+    // on the ComputeTaskPool:
+    //   1. prepare (sequential) - make sure every glyph needed this frame is
+    //      tessellated into `cache`. This is the only step allowed to mutate it.
+    //   2. assemble (parallel) - each worker reads the now-stable `cache` to
+    //      concatenate its entity's glyphs and sends the result back over an
+    //      mpsc channel; the main thread drains the channel and performs the
+    //      `commands`/`meshes` mutations sequentially.
+
+    let mut ready = Vec::new();
+
+    for (entity, transform, global_transform, material, text_mesh, color, mesh, visibility, mut state) in
+        text_meshes.iter_mut()
+    {
+        let quality = text_mesh.quality.unwrap_or(default_quality.quality);
+
+        if !ensure_cached(text_mesh, &mut fonts, &mut cache, quality) {
+            if !state.warning_shown {
+                state.warning_trigger_count += 1;
+
+                if state.warning_trigger_count > 5 {
+                    warn!("font mesh not found - did you load the font using #mesh label (`asset_server.load('font.ttf#mesh'))`");
+                    state.warning_shown = true;
+                }
+            }
+            continue;
+        }
+
+        ready.push((
+            entity,
+            *transform,
+            *global_transform,
+            material.cloned(),
+            text_mesh,
+            color.copied(),
+            mesh.cloned(),
+            *visibility,
+            quality,
+        ));
+    }
 
-    // TODO: performance - split to mesh-update and mesh-create systems?
+    let (sender, receiver) = mpsc::channel();
+    let cache_ref = &*cache;
 
-    for text_mesh in text_meshes.iter_mut() {
-        let (entity, transform, global_transform, material, text_mesh, mesh, visibility, mut state) = text_mesh;
+    ComputeTaskPool::get().scope(|scope| {
+        for (entity, _, _, _, text_mesh, _, _, _, quality) in &ready {
+            let sender = sender.clone();
+            scope.spawn(async move {
+                let mesh_data = assemble_text_mesh(text_mesh, cache_ref, *quality);
+                sender.send((*entity, mesh_data)).unwrap();
+            });
+        }
+    });
+    drop(sender);
 
-        let font = match fonts.get_mut(&text_mesh.style.font) {
-            Some(font) => font,
-            None => {
-                if !state.warning_shown {
-                    state.warning_trigger_count += 1;
+    // every glyph pinned by this frame's `ensure_cached` calls has now been
+    // read back by `assemble_text_mesh` above - safe to drop the pins and
+    // let eviction catch up to the capacity bound.
+    cache.end_frame();
 
-                    if state.warning_trigger_count > 5 {
-                        warn!("font mesh not found - did you load the font using #mesh label (`asset_server.load('font.ttf#mesh'))`");
-                        state.warning_shown = true;
-                    }
-                }
-                continue;
-            }
-        };
+    let mut mesh_data_by_entity: HashMap<Entity, MeshData> = receiver.into_iter().collect();
 
-        let ttf2_mesh = generate_text_mesh(&text_mesh, &mut font.ttf_font, Some(&mut cache));
+    for (entity, transform, global_transform, material, _text_mesh, color, mesh, visibility, _quality) in ready {
+        let ttf2_mesh = mesh_data_by_entity.remove(&entity).expect(
+            "every ready entity had a worker spawned for it and that worker always sends a result",
+        );
 
         match mesh {
             Some(mesh) => {
-                let mesh = meshes.get_mut(mesh).unwrap();
+                let mesh = meshes.get_mut(&mesh).unwrap();
                 apply_mesh(ttf2_mesh, mesh);
-
-                // TODO: handle color updates
             }
             None => {
                 let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
 
                 apply_mesh(ttf2_mesh, &mut mesh);
 
+                let base_color = color.unwrap_or_default().0;
+
                 commands.entity(entity).insert(PbrBundle {
                     mesh: meshes.add(mesh),
-                    material: material.map(|m| m.clone()).unwrap_or_else(|| {
+                    material: material.unwrap_or_else(|| {
                         materials.add(StandardMaterial {
-                            base_color: text_mesh.style.color,
+                            base_color,
                             unlit: true,
                             alpha_mode: AlphaMode::Blend,
                             ..Default::default()
                         })
                     }),
-                    transform: transform.clone(),
-                    global_transform: global_transform.clone(),
-                    visibility: visibility.clone(),
+                    transform,
+                    global_transform,
+                    visibility,
                     ..Default::default()
                 });
             }
@@ -92,6 +143,48 @@ pub(crate) fn text_mesh(
     }
 }
 
+/// Applies `TextMeshColor` changes to the entity's material without touching
+/// the cached mesh - recoloring a `TextMesh` should never re-trigger
+/// tessellation.
+pub(crate) fn text_mesh_color(
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    changed: Query<(&Handle<StandardMaterial>, &TextMeshColor), Changed<TextMeshColor>>,
+) {
+    for (material, color) in changed.iter() {
+        if let Some(material) = materials.get_mut(material) {
+            material.base_color = color.0;
+        }
+    }
+}
+
+/// Regenerates every `TextMesh` that has no `quality` override of its own at
+/// the (possibly rescaled) default quality whenever the window's scale factor changes, mirroring how `bevy_text`
+/// rebuilds its layout on scale-factor change.
+pub(crate) fn rescale_on_window_scale_factor_changed(
+    mut events: EventReader<bevy::window::WindowScaleFactorChanged>,
+    mut default_quality: ResMut<DefaultTextMeshQuality>,
+    mut text_meshes: Query<&mut TextMesh>,
+) {
+    for event in events.iter() {
+        if !default_quality.scale_with_window {
+            continue;
+        }
+
+        default_quality.rescale_for_dpi(event.scale_factor as f32);
+
+        for mut text_mesh in text_meshes.iter_mut() {
+            // entities with an explicit quality override never read
+            // `default_quality`, so rescaling it can't change their mesh -
+            // regenerating them here would just be wasted retessellation
+            if text_mesh.quality.is_none() {
+                // no-op write, just to mark the component `Changed` so
+                // `text_mesh` regenerates it at the new quality
+                text_mesh.set_changed();
+            }
+        }
+    }
+}
+
 pub(crate) fn font_loaded(
     mut events: EventReader<AssetEvent<TextMeshFont>>,
     mut query: Query<(&mut TextMeshState, &TextMesh)>,
@@ -103,7 +196,7 @@ pub(crate) fn font_loaded(
         match event {
             AssetEvent::Created { handle } => {
                 for (mut state, text_mesh) in query.iter_mut() {
-                    if handle == &text_mesh.style.font {
+                    if text_mesh.sections.iter().any(|s| &s.style.font == handle) {
                         state.font_loaded = Some(true);
                     }
                 }
@@ -111,7 +204,7 @@ pub(crate) fn font_loaded(
             AssetEvent::Removed { handle } => {
                 // why would this happen? handling anyway
                 for (mut state, text_mesh) in query.iter_mut() {
-                    if handle == &text_mesh.style.font {
+                    if text_mesh.sections.iter().any(|s| &s.style.font == handle) {
                         state.font_loaded = Some(false);
                     }
                 }
@@ -125,7 +218,7 @@ pub(crate) fn font_loaded(
 pub struct TextMeshState {
     // this state matters only when the fonts have not been loaded yet
     // will be None for text bundles spawned when fonts have are already loaded
-    font_loaded: Option<bool>,
+    pub(crate) font_loaded: Option<bool>,
 
     warning_trigger_count: usize,
     warning_shown: bool,
@@ -141,9 +234,10 @@ impl Default for TextMeshState {
     }
 }
 
-fn apply_mesh(mesh_data: MeshData, mesh: &mut Mesh) {
+pub(crate) fn apply_mesh(mesh_data: MeshData, mesh: &mut Mesh) {
     mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, mesh_data.vertices);
     mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, mesh_data.normals);
     mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, mesh_data.uvs);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, mesh_data.colors);
     mesh.set_indices(Some(Indices::U32(mesh_data.indices)));
 }
@@ -0,0 +1,515 @@
+//! Optional text-shaping backend, enabled with the `shaping` feature.
+//!
+//! The default pipeline ([`crate::mesh_data_generator`]) tessellates one
+//! outline per codepoint straight from the raw TTF font, so it has no real
+//! shaping: no kerning, no ligatures, no bidi, and no fallback when a font is
+//! missing a glyph. This module instead runs each section's text through
+//! `cosmic-text` to get a laid-out run of positioned, fallback-resolved
+//! glyphs, and pulls each glyph's vector outline from `swash` to feed into
+//! the crate's existing triangulation/extrusion step.
+//!
+//! Glyphs are cached in [`MeshCache`] keyed by the *shaped* glyph id
+//! (`GlyphKey::Shaped`) rather than by codepoint, since shaping can resolve
+//! the same codepoint to different glyphs depending on fallback.
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use bevy::prelude::*;
+use cosmic_text::{fontdb, Attrs, Buffer, Family, FontSystem, Metrics, Shaping as ShapingMode};
+use swash::scale::{Render, ScaleContext, Source, StrikeWith};
+use swash::zeno::Command;
+use swash::FontRef;
+
+use crate::font_loader::TextMeshFont;
+use crate::mesh_cache::MeshCache;
+use crate::mesh_data_generator::MeshData;
+use crate::mesh_system::{apply_mesh, TextMeshState};
+use crate::text_mesh::{DefaultTextMeshQuality, TextMesh, TextMeshColor, TextMeshQuality};
+
+const DEPTH: f32 = 1.0;
+
+/// Owns the `cosmic-text`/`swash` state needed to shape and rasterize glyph
+/// outlines. Kept as a resource so fonts stay registered and scratch buffers
+/// are reused across frames.
+#[derive(Resource)]
+pub struct ShapingContext {
+    pub(crate) font_system: FontSystem,
+    scale_context: ScaleContext,
+    /// `TextMeshFont`s already registered into `font_system`'s `fontdb`,
+    /// keyed by handle and mapped to the family name to shape against - so
+    /// the same font's bytes aren't re-parsed into `fontdb` every frame.
+    registered_fonts: HashMap<Handle<TextMeshFont>, String>,
+}
+
+impl Default for ShapingContext {
+    fn default() -> Self {
+        Self {
+            font_system: FontSystem::new(),
+            scale_context: ScaleContext::new(),
+            registered_fonts: HashMap::new(),
+        }
+    }
+}
+
+impl ShapingContext {
+    /// Registers `font`'s raw bytes into the `fontdb` backing `font_system`
+    /// the first time `handle` is seen, returning the family name to shape
+    /// against - so the shaped path renders the same font `TextMeshStyle`
+    /// pointed at, rather than whatever `cosmic-text`'s own fallback
+    /// resolution would otherwise pick.
+    fn font_family_for(&mut self, handle: &Handle<TextMeshFont>, font: &TextMeshFont) -> String {
+        if let Some(family) = self.registered_fonts.get(handle) {
+            return family.clone();
+        }
+
+        let db = self.font_system.db_mut();
+        let known_ids: std::collections::HashSet<fontdb::ID> = db.faces().map(|face| face.id).collect();
+        db.load_font_data(font.bytes.clone());
+        let family = db
+            .faces()
+            .find(|face| !known_ids.contains(&face.id))
+            .and_then(|face| face.families.first())
+            .map(|(name, _)| name.clone())
+            .expect("load_font_data always adds at least one face with a family name");
+
+        self.registered_fonts.insert(handle.clone(), family.clone());
+        family
+    }
+}
+
+/// Shaped-backend equivalent of [`crate::mesh_system::text_mesh`]: shapes and
+/// tessellates every section of each changed `TextMesh` via `cosmic-text` +
+/// `swash` instead of the per-codepoint ttf2mesh path.
+pub(crate) fn text_mesh_shaped(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut text_meshes: Query<
+        (
+            Entity,
+            &Transform,
+            &GlobalTransform,
+            Option<&Handle<StandardMaterial>>,
+            &TextMesh,
+            Option<&TextMeshColor>,
+            Option<&Handle<Mesh>>,
+            &Visibility,
+            // only read as a re-trigger signal (see below) - never written
+            // from this system, so fetching it doesn't mark it `Changed`
+            // and cause the `Or` filter to match every frame.
+            &TextMeshState,
+        ),
+        Or<(Changed<TextMesh>, Changed<TextMeshState>)>,
+    >,
+    mut cache: ResMut<MeshCache>,
+    mut shaping: ResMut<ShapingContext>,
+    default_quality: Res<DefaultTextMeshQuality>,
+    fonts: Res<Assets<TextMeshFont>>,
+) {
+    // the shaping backend still renders the `TextMeshFont` asset each
+    // section's `TextMeshStyle::font` points at (registered into
+    // `cosmic-text`'s fontdb on first use - see `font_family_for`), so like
+    // the default ttf2mesh path it waits on that asset handle and skips a
+    // `TextMesh` whose font hasn't loaded yet. Unlike that path it never
+    // writes `TextMeshState` itself (doing so every frame would mark it
+    // `Changed` unconditionally and defeat the `Changed<TextMesh>` half of
+    // the filter above - see `47c213d`) - it only reads it so that
+    // `mesh_system::font_loaded` marking `TextMeshState` changed when the
+    // font asset finishes loading re-triggers this system for the skipped
+    // entity.
+    for (entity, transform, global_transform, material, text_mesh, color, mesh, visibility, _state) in
+        text_meshes.iter_mut()
+    {
+        if !text_mesh.sections.iter().all(|section| fonts.get(&section.style.font).is_some()) {
+            continue;
+        }
+
+        let quality = text_mesh.quality.unwrap_or(default_quality.quality);
+        let mesh_data = shape_text_mesh(text_mesh, &mut shaping, &mut cache, quality, &fonts);
+
+        match mesh {
+            Some(mesh) => {
+                let mesh = meshes.get_mut(mesh).unwrap();
+                apply_mesh(mesh_data, mesh);
+            }
+            None => {
+                let mut mesh = Mesh::new(bevy::render::render_resource::PrimitiveTopology::TriangleList);
+                apply_mesh(mesh_data, &mut mesh);
+
+                let base_color = color.unwrap_or_default().0;
+
+                commands.entity(entity).insert(PbrBundle {
+                    mesh: meshes.add(mesh),
+                    material: material.map(|m| m.clone()).unwrap_or_else(|| {
+                        materials.add(StandardMaterial {
+                            base_color,
+                            unlit: true,
+                            alpha_mode: AlphaMode::Blend,
+                            ..Default::default()
+                        })
+                    }),
+                    transform: *transform,
+                    global_transform: *global_transform,
+                    visibility: *visibility,
+                    ..Default::default()
+                });
+            }
+        }
+    }
+}
+
+fn shape_text_mesh(
+    text_mesh: &TextMesh,
+    shaping: &mut ShapingContext,
+    cache: &mut MeshCache,
+    quality: TextMeshQuality,
+    fonts: &Assets<TextMeshFont>,
+) -> MeshData {
+    let mut data = MeshData::default();
+    // accumulated along the baseline across sections, mirroring
+    // `mesh_data_generator::assemble_text_mesh` - without this every
+    // section's glyphs would start back at x ≈ 0 and stack on top of
+    // each other instead of laying out in sequence.
+    let mut cursor = 0.0;
+
+    for section in &text_mesh.sections {
+        let color = section.color.as_rgba_f32();
+        let size = section.style.font_size;
+        let metrics = Metrics::new(size, size);
+
+        let font = fonts
+            .get(&section.style.font)
+            .expect("caller only calls shape_text_mesh once every section's font has loaded");
+        let family = shaping.font_family_for(&section.style.font, font);
+
+        let mut buffer = Buffer::new(&mut shaping.font_system, metrics);
+        buffer.set_text(
+            &mut shaping.font_system,
+            &section.text,
+            Attrs::new().family(Family::Name(&family)),
+            ShapingMode::Advanced,
+        );
+        buffer.shape_until_scroll(&mut shaping.font_system);
+
+        let mut section_width = 0.0_f32;
+
+        for run in buffer.layout_runs() {
+            for glyph in run.glyphs {
+                let font_id = font_id_key(glyph.font_id);
+                let glyph_id = glyph.glyph_id;
+
+                let outline = match cache.get_shaped(font_id, glyph_id, size, DEPTH, quality) {
+                    Some(cached) => cached.clone(),
+                    None => {
+                        let tessellated = tessellate_shaped_glyph(shaping, glyph.font_id, glyph_id, size, quality);
+                        cache.insert_shaped(font_id, glyph_id, size, DEPTH, quality, tessellated.clone());
+                        tessellated
+                    }
+                };
+
+                append_at(&mut data, &outline, cursor + glyph.x, color);
+                section_width = section_width.max(glyph.x + glyph.w);
+            }
+        }
+
+        cursor += section_width;
+    }
+
+    data
+}
+
+/// `cosmic_text::fontdb::ID` doesn't expose a stable integer, so hash it down
+/// to something `Copy`/`Hash`-able for [`MeshCache`]'s key.
+fn font_id_key(id: cosmic_text::fontdb::ID) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    id.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn tessellate_shaped_glyph(
+    shaping: &mut ShapingContext,
+    font_id: cosmic_text::fontdb::ID,
+    glyph_id: u16,
+    size: f32,
+    quality: TextMeshQuality,
+) -> MeshData {
+    let font = shaping
+        .font_system
+        .get_font(font_id)
+        .expect("cosmic-text only yields glyphs from fonts it successfully resolved");
+    let font_ref = FontRef::from_index(font.data(), 0).expect("font data loaded by cosmic-text is valid");
+
+    let mut scaler = shaping.scale_context.builder(font_ref).size(size).build();
+
+    let mut path = Vec::new();
+    Render::new(&[Source::Outline])
+        .format(StrikeWith::default())
+        .render_outline(&mut scaler, glyph_id, |command| path.push(command));
+
+    tessellate_outline_commands(&path, DEPTH, quality)
+}
+
+/// Derives a Bézier subdivision step count from `quality`'s curve tolerance
+/// (mirroring `mesh_data_generator::to_ttf2mesh_quality`'s role for the
+/// ttf2mesh path) - finer tolerance (higher quality) subdivides into more
+/// segments. `TextMeshQuality::Medium`'s tolerance (0.05) maps to 8 segments,
+/// matching this module's previous fixed step count.
+fn curve_segments(quality: TextMeshQuality) -> usize {
+    let tolerance = quality.curve_tolerance();
+
+    ((0.4 / tolerance.max(f32::EPSILON)).round() as usize).clamp(4, 64)
+}
+
+/// Triangulates and extrudes a swash vector outline (move/line/quad/cubic
+/// path commands) into a closed 3D mesh.
+///
+/// Each contour is fan-triangulated from its first vertex for the front and
+/// back faces and given flat-shaded side walls. This assumes roughly convex,
+/// non-self-intersecting contours - glyphs with counters (e.g. 'o', 'a')
+/// have an inner and outer contour that this fan does not reconcile, so
+/// their counters render filled in rather than as a hole.
+fn tessellate_outline_commands(commands: &[Command], depth: f32, quality: TextMeshQuality) -> MeshData {
+    let mut data = MeshData::default();
+    let segments = curve_segments(quality);
+
+    for contour in flatten_contours(commands, segments) {
+        extrude_contour(&contour, depth, &mut data);
+    }
+
+    data
+}
+
+fn flatten_contours(commands: &[Command], segments: usize) -> Vec<Vec<(f32, f32)>> {
+    let mut contours = Vec::new();
+    let mut current: Vec<(f32, f32)> = Vec::new();
+    let mut cursor = (0.0_f32, 0.0_f32);
+
+    let mut close_current = |current: &mut Vec<(f32, f32)>, contours: &mut Vec<Vec<(f32, f32)>>| {
+        if current.len() >= 3 {
+            contours.push(std::mem::take(current));
+        } else {
+            current.clear();
+        }
+    };
+
+    for command in commands {
+        match *command {
+            Command::MoveTo(p) => {
+                close_current(&mut current, &mut contours);
+                cursor = (p.x, p.y);
+                current.push(cursor);
+            }
+            Command::LineTo(p) => {
+                cursor = (p.x, p.y);
+                current.push(cursor);
+            }
+            Command::QuadTo(c, p) => {
+                for i in 1..=segments {
+                    let t = i as f32 / segments as f32;
+                    current.push(quad_point(cursor, (c.x, c.y), (p.x, p.y), t));
+                }
+                cursor = (p.x, p.y);
+            }
+            Command::CurveTo(c1, c2, p) => {
+                for i in 1..=segments {
+                    let t = i as f32 / segments as f32;
+                    current.push(cubic_point(cursor, (c1.x, c1.y), (c2.x, c2.y), (p.x, p.y), t));
+                }
+                cursor = (p.x, p.y);
+            }
+            Command::Close => close_current(&mut current, &mut contours),
+        }
+    }
+    close_current(&mut current, &mut contours);
+
+    contours
+}
+
+fn quad_point(p0: (f32, f32), p1: (f32, f32), p2: (f32, f32), t: f32) -> (f32, f32) {
+    let mt = 1.0 - t;
+    (
+        mt * mt * p0.0 + 2.0 * mt * t * p1.0 + t * t * p2.0,
+        mt * mt * p0.1 + 2.0 * mt * t * p1.1 + t * t * p2.1,
+    )
+}
+
+fn cubic_point(p0: (f32, f32), p1: (f32, f32), p2: (f32, f32), p3: (f32, f32), t: f32) -> (f32, f32) {
+    let mt = 1.0 - t;
+    (
+        mt * mt * mt * p0.0 + 3.0 * mt * mt * t * p1.0 + 3.0 * mt * t * t * p2.0 + t * t * t * p3.0,
+        mt * mt * mt * p0.1 + 3.0 * mt * mt * t * p1.1 + 3.0 * mt * t * t * p2.1 + t * t * t * p3.1,
+    )
+}
+
+/// Fan-triangulates `points` for front/back faces at `z = 0`/`z = -depth` and
+/// adds flat-shaded side walls along each edge, appending into `data`.
+fn extrude_contour(points: &[(f32, f32)], depth: f32, data: &mut MeshData) {
+    let n = points.len();
+    if n < 3 {
+        return;
+    }
+
+    let base = data.vertices.len() as u32;
+
+    for &(x, y) in points {
+        data.vertices.push([x, y, 0.0]);
+        data.normals.push([0.0, 0.0, 1.0]);
+        data.uvs.push([x, y]);
+    }
+    for &(x, y) in points {
+        data.vertices.push([x, y, -depth]);
+        data.normals.push([0.0, 0.0, -1.0]);
+        data.uvs.push([x, y]);
+    }
+
+    for i in 1..n as u32 - 1 {
+        data.indices.extend([base, base + i, base + i + 1]);
+        data.indices
+            .extend([base + n as u32, base + n as u32 + i + 1, base + n as u32 + i]);
+    }
+
+    for i in 0..n {
+        let next = (i + 1) % n;
+        let (ax, ay) = points[i];
+        let (bx, by) = points[next];
+        let normal = side_normal((ax, ay), (bx, by));
+
+        let idx = data.vertices.len() as u32;
+        data.vertices.push([ax, ay, 0.0]);
+        data.vertices.push([bx, by, 0.0]);
+        data.vertices.push([ax, ay, -depth]);
+        data.vertices.push([bx, by, -depth]);
+        for _ in 0..4 {
+            data.normals.push(normal);
+            data.uvs.push([0.0, 0.0]);
+        }
+
+        data.indices.extend([idx, idx + 2, idx + 1]);
+        data.indices.extend([idx + 1, idx + 2, idx + 3]);
+    }
+}
+
+fn side_normal(a: (f32, f32), b: (f32, f32)) -> [f32; 3] {
+    let dir = (b.0 - a.0, b.1 - a.1);
+    let len = (dir.0 * dir.0 + dir.1 * dir.1).sqrt().max(f32::EPSILON);
+    [dir.1 / len, -dir.0 / len, 0.0]
+}
+
+fn append_at(data: &mut MeshData, glyph_data: &MeshData, x_offset: f32, color: [f32; 4]) {
+    let index_offset = data.vertices.len() as u32;
+
+    data.vertices.extend(
+        glyph_data
+            .vertices
+            .iter()
+            .map(|[x, y, z]| [x + x_offset, *y, *z]),
+    );
+    data.normals.extend(&glyph_data.normals);
+    data.uvs.extend(&glyph_data.uvs);
+    data.colors
+        .extend(std::iter::repeat(color).take(glyph_data.vertices.len()));
+    data.indices
+        .extend(glyph_data.indices.iter().map(|i| i + index_offset));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use swash::zeno::Point;
+
+    fn point(x: f32, y: f32) -> Point {
+        Point { x, y }
+    }
+
+    #[test]
+    fn quad_point_endpoints_match_control_points() {
+        let (p0, p1, p2) = ((0.0, 0.0), (1.0, 2.0), (2.0, 0.0));
+
+        assert_eq!(quad_point(p0, p1, p2, 0.0), p0);
+        assert_eq!(quad_point(p0, p1, p2, 1.0), p2);
+    }
+
+    #[test]
+    fn cubic_point_endpoints_match_control_points() {
+        let (p0, p1, p2, p3) = ((0.0, 0.0), (1.0, 1.0), (2.0, 1.0), (3.0, 0.0));
+
+        assert_eq!(cubic_point(p0, p1, p2, p3, 0.0), p0);
+        assert_eq!(cubic_point(p0, p1, p2, p3, 1.0), p3);
+    }
+
+    #[test]
+    fn flatten_contours_drops_a_degenerate_less_than_3_point_contour() {
+        let commands = [
+            Command::MoveTo(point(0.0, 0.0)),
+            Command::LineTo(point(1.0, 0.0)),
+            Command::Close,
+        ];
+
+        assert!(flatten_contours(&commands, 8).is_empty());
+    }
+
+    #[test]
+    fn flatten_contours_closes_a_simple_triangle() {
+        let commands = [
+            Command::MoveTo(point(0.0, 0.0)),
+            Command::LineTo(point(1.0, 0.0)),
+            Command::LineTo(point(0.0, 1.0)),
+            Command::Close,
+        ];
+
+        let contours = flatten_contours(&commands, 8);
+
+        assert_eq!(contours.len(), 1);
+        assert_eq!(contours[0].len(), 3);
+    }
+
+    #[test]
+    fn flatten_contours_subdivides_a_curve_into_segments_points() {
+        let commands = [
+            Command::MoveTo(point(0.0, 0.0)),
+            Command::QuadTo(point(1.0, 1.0), point(2.0, 0.0)),
+            Command::Close,
+        ];
+
+        let segments = 8;
+        let contours = flatten_contours(&commands, segments);
+
+        assert_eq!(contours.len(), 1);
+        // the initial MoveTo point, plus `segments` subdivided points
+        assert_eq!(contours[0].len(), 1 + segments);
+    }
+
+    #[test]
+    fn curve_segments_is_finer_at_higher_quality() {
+        assert!(curve_segments(TextMeshQuality::High) > curve_segments(TextMeshQuality::Medium));
+        assert!(curve_segments(TextMeshQuality::Medium) > curve_segments(TextMeshQuality::Low));
+        // unchanged from this module's previous fixed step count, so
+        // existing shaped-glyph cache entries at the default quality keep
+        // the same geometry
+        assert_eq!(curve_segments(TextMeshQuality::Medium), 8);
+    }
+
+    #[test]
+    fn extrude_contour_produces_fan_and_side_wall_geometry_for_a_triangle() {
+        let triangle = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)];
+        let mut data = MeshData::default();
+
+        extrude_contour(&triangle, 1.0, &mut data);
+
+        // front + back fan vertices, plus 4 side-wall vertices per edge
+        assert_eq!(data.vertices.len(), 2 * 3 + 3 * 4);
+        // 1 front + 1 back fan triangle, plus 2 side triangles per edge, 3 indices each
+        assert_eq!(data.indices.len(), (2 + 3 * 2) * 3);
+    }
+
+    #[test]
+    fn extrude_contour_ignores_a_degenerate_contour() {
+        let mut data = MeshData::default();
+
+        extrude_contour(&[(0.0, 0.0), (1.0, 0.0)], 1.0, &mut data);
+
+        assert!(data.vertices.is_empty());
+        assert!(data.indices.is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+use bevy::prelude::*;
+
+use crate::font_loader::TextMeshFont;
+
+/// Font and layout properties shared by every glyph in a [`TextMesh`].
+///
+/// Colour is intentionally *not* part of this struct - see [`TextMeshColor`].
+#[derive(Debug, Clone)]
+pub struct TextMeshStyle {
+    pub font: Handle<TextMeshFont>,
+    pub font_size: f32,
+}
+
+/// One run of text within a [`TextMesh`], with its own font, size and colour.
+///
+/// Sections are laid out in sequence along the baseline, so a single
+/// `TextMesh` can mix fonts, sizes and colours - e.g. a bold highlighted
+/// word inside a sentence.
+///
+/// `color` is baked into the section's own vertices
+/// (`Mesh::ATTRIBUTE_COLOR`), so changing it costs a full retessellation -
+/// it's for per-section colour that genuinely differs within one `TextMesh`.
+/// For recolouring a whole `TextMesh` cheaply (no retessellation), use
+/// [`TextMeshColor`] instead; the two multiply together (Bevy multiplies
+/// `StandardMaterial::base_color` by vertex colour), so leaving `color` at
+/// its [`Color::WHITE`] default lets `TextMeshColor` fully control the
+/// result.
+#[derive(Debug, Clone)]
+pub struct TextMeshSection {
+    pub text: String,
+    pub style: TextMeshStyle,
+    pub color: Color,
+}
+
+/// A 3D text entity, tessellated into a [`Mesh`](bevy::render::mesh::Mesh) by
+/// [`crate::mesh_system::text_mesh`].
+#[derive(Debug, Component, Clone)]
+pub struct TextMesh {
+    pub sections: Vec<TextMeshSection>,
+    /// How finely glyph curves are subdivided. `None` defers to
+    /// [`DefaultTextMeshQuality`].
+    pub quality: Option<TextMeshQuality>,
+}
+
+impl TextMesh {
+    /// Convenience constructor for the common single-style case. The section
+    /// colour defaults to [`Color::WHITE`] (a no-op tint), so recolour the
+    /// result with [`TextMeshColor`] rather than retessellating - use
+    /// [`Self::from_section_with_color`] if the section itself needs a baked
+    /// colour instead (e.g. to mix with other sections that have their own).
+    pub fn from_section(text: impl Into<String>, style: TextMeshStyle) -> Self {
+        Self::from_section_with_color(text, style, Color::WHITE)
+    }
+
+    /// Like [`Self::from_section`], but bakes `color` into the section's
+    /// vertices instead of defaulting to white.
+    pub fn from_section_with_color(text: impl Into<String>, style: TextMeshStyle, color: Color) -> Self {
+        Self {
+            sections: vec![TextMeshSection {
+                text: text.into(),
+                style,
+                color,
+            }],
+            quality: None,
+        }
+    }
+}
+
+/// Controls how finely Bézier segments in a glyph outline are subdivided
+/// before triangulation: coarser settings use fewer triangles but facet
+/// visibly on large text, finer settings look smoother but cost more
+/// triangles on small/distant text.
+///
+/// Included in the [`crate::mesh_cache::MeshCache`] key, so differently
+/// tessellated instances of the same glyph don't collide.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextMeshQuality {
+    Low,
+    Medium,
+    High,
+    /// Curve flatness tolerance, in the font's own units - smaller is finer.
+    Custom(f32),
+}
+
+impl TextMeshQuality {
+    pub(crate) fn curve_tolerance(self) -> f32 {
+        match self {
+            Self::Low => 0.1,
+            Self::Medium => 0.05,
+            Self::High => 0.01,
+            Self::Custom(tolerance) => tolerance,
+        }
+    }
+
+    /// Rescales `self` (treated as the un-rescaled baseline tolerance) for
+    /// `scale_factor`, so the same perceived smoothness is kept as the
+    /// window's DPI changes - finer subdivision at higher DPI.
+    ///
+    /// Always rescale from the original baseline, not from a previously
+    /// rescaled value - doing the latter compounds drift across repeated
+    /// `WindowScaleFactorChanged` events.
+    pub fn scaled_for_dpi(self, scale_factor: f32) -> Self {
+        Self::Custom(self.curve_tolerance() / scale_factor.max(0.01))
+    }
+}
+
+impl Default for TextMeshQuality {
+    fn default() -> Self {
+        Self::Medium
+    }
+}
+
+/// The [`TextMeshQuality`] used by any [`TextMesh`] that doesn't set its own.
+#[derive(Debug, Resource, Clone, Copy)]
+pub struct DefaultTextMeshQuality {
+    pub quality: TextMeshQuality,
+    /// When `true`, `quality` is rescaled on `WindowScaleFactorChanged` via
+    /// [`TextMeshQuality::scaled_for_dpi`].
+    pub scale_with_window: bool,
+    /// The un-rescaled quality `quality` was last derived from.
+    ///
+    /// Rescaling always starts from this baseline rather than from `quality`
+    /// itself, so repeated `WindowScaleFactorChanged` events (duplicates, or
+    /// DPI moving back and forth) track the real scale factor instead of
+    /// compounding drift from the previous rescale.
+    base_quality: TextMeshQuality,
+}
+
+impl DefaultTextMeshQuality {
+    /// Rescales `base_quality` for `scale_factor` and stores the result in
+    /// `quality`, leaving `base_quality` untouched.
+    pub(crate) fn rescale_for_dpi(&mut self, scale_factor: f32) {
+        self.quality = self.base_quality.scaled_for_dpi(scale_factor);
+    }
+}
+
+impl Default for DefaultTextMeshQuality {
+    fn default() -> Self {
+        let quality = TextMeshQuality::default();
+
+        Self {
+            quality,
+            scale_with_window: true,
+            base_quality: quality,
+        }
+    }
+}
+
+/// A whole-entity colour tint for a [`TextMesh`], applied at the material
+/// level (`StandardMaterial::base_color`).
+///
+/// Kept separate from [`TextMeshStyle`] so that recolouring a `TextMesh` only
+/// updates the entity's `StandardMaterial` and does not trigger the
+/// (expensive) glyph tessellation path again. It multiplies with each
+/// section's own baked [`TextMeshSection::color`] rather than replacing it -
+/// see that field's doc comment for the full precedence.
+#[derive(Debug, Component, Clone, Copy)]
+pub struct TextMeshColor(pub Color);
+
+impl Default for TextMeshColor {
+    fn default() -> Self {
+        Self(Color::WHITE)
+    }
+}
+
+impl From<Color> for TextMeshColor {
+    fn from(color: Color) -> Self {
+        Self(color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scaled_for_dpi_rescales_from_the_call_receiver() {
+        let base = TextMeshQuality::Custom(0.1);
+
+        assert_eq!(base.scaled_for_dpi(2.0).curve_tolerance(), 0.05);
+    }
+
+    #[test]
+    fn rescale_for_dpi_does_not_compound_across_repeated_calls() {
+        let mut default_quality = DefaultTextMeshQuality {
+            quality: TextMeshQuality::Custom(0.1),
+            scale_with_window: true,
+            base_quality: TextMeshQuality::Custom(0.1),
+        };
+
+        default_quality.rescale_for_dpi(2.0);
+        default_quality.rescale_for_dpi(2.0);
+
+        assert_eq!(default_quality.quality.curve_tolerance(), 0.05);
+    }
+}